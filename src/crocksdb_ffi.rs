@@ -0,0 +1,91 @@
+// Copyright 2017 PingCAP, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Raw FFI bindings onto the `crocksdb` C shim that wraps RocksDB's C++ API.
+//! Only the declarations used by `table_properties_collector.rs` are listed
+//! here; the rest of the real bindings live alongside this file.
+
+use libc::{c_char, c_int, c_void, size_t};
+
+pub enum DBTablePropertiesCollector {}
+pub enum DBUserCollectedProperties {}
+pub enum DBTablePropertiesCollectorFactory {}
+pub enum DBOptions {}
+
+/// Mirrors RocksDB's internal `EntryType`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DBEntryType {
+    Put = 0,
+    Delete = 1,
+    SingleDelete = 2,
+    Merge = 3,
+    RangeDeletion = 4,
+    BlobIndex = 5,
+    Other = 6,
+}
+
+/// Mirrors RocksDB's internal `TablePropertiesCollectorFactory::Context::TableFileCreationReason`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DBTableFileCreationReason {
+    Flush = 0,
+    Compaction = 1,
+    Recovery = 2,
+    Misc = 3,
+}
+
+pub type NameFn = extern "C" fn(*mut c_void) -> *const c_char;
+pub type DestructFn = extern "C" fn(*mut c_void);
+pub type AddFn = extern "C" fn(*mut c_void, *const u8, size_t, *const u8, size_t, c_int, u64, u64);
+pub type FinishFn = extern "C" fn(*mut c_void, *mut DBUserCollectedProperties);
+pub type ReadablePropertiesFn = extern "C" fn(*mut c_void, *mut DBUserCollectedProperties);
+pub type BlockAddFn = extern "C" fn(*mut c_void, u64, u64, u64);
+pub type NeedCompactFn = extern "C" fn(*const c_void) -> bool;
+pub type CreateTablePropertiesCollectorFn =
+    extern "C" fn(*mut c_void, u32, DBTableFileCreationReason) -> *mut DBTablePropertiesCollector;
+
+extern "C" {
+    // The parameter order here must match the positional arguments passed at
+    // every `new_table_properties_collector` call site field-for-field.
+    pub fn crocksdb_table_properties_collector_create(
+        state: *mut c_void,
+        name: NameFn,
+        destruct: DestructFn,
+        add: AddFn,
+        finish: FinishFn,
+        readable_properties: ReadablePropertiesFn,
+        block_add: BlockAddFn,
+        need_compact: NeedCompactFn,
+    ) -> *mut DBTablePropertiesCollector;
+
+    pub fn crocksdb_table_properties_collector_factory_create(
+        state: *mut c_void,
+        name: NameFn,
+        destruct: DestructFn,
+        create_table_properties_collector: CreateTablePropertiesCollectorFn,
+    ) -> *mut DBTablePropertiesCollectorFactory;
+
+    pub fn crocksdb_options_add_table_properties_collector_factory(
+        options: *mut DBOptions,
+        factory: *mut DBTablePropertiesCollectorFactory,
+    );
+
+    pub fn crocksdb_user_collected_properties_add(
+        props: *mut DBUserCollectedProperties,
+        key: *const u8,
+        key_len: size_t,
+        value: *const u8,
+        value_len: size_t,
+    );
+}
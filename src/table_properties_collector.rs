@@ -11,12 +11,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crocksdb_ffi::{self, DBEntryType, DBTablePropertiesCollector, DBUserCollectedProperties};
+use crocksdb_ffi::{
+    self, DBEntryType, DBOptions, DBTableFileCreationReason, DBTablePropertiesCollector,
+    DBTablePropertiesCollectorFactory, DBUserCollectedProperties,
+};
 use libc::{c_char, c_int, c_void, size_t};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
 use std::ffi::CString;
 use std::mem;
 use std::slice;
+use std::sync::Mutex;
 
 /// `TablePropertiesCollector` provides the mechanism for users to collect
 /// their own properties that they are interested in. This class is essentially
@@ -32,9 +38,28 @@ pub trait TablePropertiesCollector {
     /// writing the properties block.
     fn finish(&mut self) -> HashMap<Vec<u8>, Vec<u8>>;
 
+    /// Will be called once per data block, after the block has been built and
+    /// compressed, so collectors can observe raw vs. compressed block sizes
+    /// without re-scanning every value.
+    fn block_add(
+        &mut self,
+        _block_raw_bytes: u64,
+        _block_compressed_bytes_fast: u64,
+        _block_compressed_bytes_slow: u64,
+    ) {
+    }
+
     fn need_compact(&self) -> bool {
         false
     }
+
+    /// Returns a human-readable view of the properties collected, for tools like
+    /// `sst_dump` where the binary encoding used by `finish` (varints, packed
+    /// structs) would otherwise be unreadable. Defaults to empty, leaving the
+    /// on-disk property block as the sole compact binary representation.
+    fn readable_properties(&self) -> HashMap<String, String> {
+        HashMap::new()
+    }
 }
 
 struct TablePropertiesCollectorHandle<T: TablePropertiesCollector> {
@@ -102,6 +127,45 @@ pub extern "C" fn finish<T: TablePropertiesCollector>(
     }
 }
 
+// Signature must match `crocksdb_ffi::ReadablePropertiesFn` and the
+// `readable_properties` slot passed to
+// `crocksdb_table_properties_collector_create` below.
+pub extern "C" fn readable_properties<T: TablePropertiesCollector>(
+    handle: *mut c_void,
+    props: *mut DBUserCollectedProperties,
+) {
+    unsafe {
+        let handle = &mut *(handle as *mut TablePropertiesCollectorHandle<T>);
+        for (key, value) in handle.rep.readable_properties() {
+            crocksdb_ffi::crocksdb_user_collected_properties_add(
+                props,
+                key.as_ptr(),
+                key.len(),
+                value.as_ptr(),
+                value.len(),
+            );
+        }
+    }
+}
+
+// Signature must match `crocksdb_ffi::BlockAddFn` and the `block_add` slot
+// passed to `crocksdb_table_properties_collector_create` below.
+pub extern "C" fn block_add<T: TablePropertiesCollector>(
+    handle: *mut c_void,
+    block_raw_bytes: u64,
+    block_compressed_bytes_fast: u64,
+    block_compressed_bytes_slow: u64,
+) {
+    unsafe {
+        let handle = &mut *(handle as *mut TablePropertiesCollectorHandle<T>);
+        handle.rep.block_add(
+            block_raw_bytes,
+            block_compressed_bytes_fast,
+            block_compressed_bytes_slow,
+        );
+    }
+}
+
 pub extern "C" fn need_compact<T: TablePropertiesCollector>(handle: *const c_void) -> bool {
     unsafe {
         let handle = &*(handle as *const TablePropertiesCollectorHandle<T>);
@@ -120,6 +184,361 @@ pub unsafe fn new_table_properties_collector<T: TablePropertiesCollector>(
         destruct::<T>,
         add::<T>,
         finish::<T>,
+        readable_properties::<T>,
+        block_add::<T>,
         need_compact::<T>,
     )
 }
+
+/// Context handed to `TablePropertiesCollectorFactory::create_table_properties_collector`
+/// for every table (SST) that is about to be built, so a factory can specialize the
+/// collector it returns instead of sharing a single instance across the whole DB.
+pub struct TablePropertiesCollectorFactoryContext {
+    /// Id of the column family the table belongs to.
+    pub column_family_id: u32,
+    /// Why this table is being created, e.g. flush, compaction or recovery.
+    pub reason: DBTableFileCreationReason,
+}
+
+/// `TablePropertiesCollectorFactory` creates a new `TablePropertiesCollector` for
+/// every table that RocksDB builds. Unlike binding a single `TablePropertiesCollector`
+/// for the whole DB, this avoids correctness bugs where per-table state (e.g. counts
+/// of deleted keys) would otherwise accumulate across every SST and be mutated
+/// concurrently by multiple flush/compaction threads.
+pub trait TablePropertiesCollectorFactory: Send + Sync {
+    /// Creates a new table properties collector. Called once per table.
+    fn create_table_properties_collector(
+        &self,
+        context: TablePropertiesCollectorFactoryContext,
+    ) -> Box<dyn TablePropertiesCollector>;
+}
+
+impl TablePropertiesCollector for Box<dyn TablePropertiesCollector> {
+    fn add(&mut self, key: &[u8], value: &[u8], entry_type: DBEntryType, seq: u64, file_size: u64) {
+        (**self).add(key, value, entry_type, seq, file_size)
+    }
+
+    fn finish(&mut self) -> HashMap<Vec<u8>, Vec<u8>> {
+        (**self).finish()
+    }
+
+    fn block_add(
+        &mut self,
+        block_raw_bytes: u64,
+        block_compressed_bytes_fast: u64,
+        block_compressed_bytes_slow: u64,
+    ) {
+        (**self).block_add(
+            block_raw_bytes,
+            block_compressed_bytes_fast,
+            block_compressed_bytes_slow,
+        )
+    }
+
+    fn need_compact(&self) -> bool {
+        (**self).need_compact()
+    }
+
+    fn readable_properties(&self) -> HashMap<String, String> {
+        (**self).readable_properties()
+    }
+}
+
+struct TablePropertiesCollectorFactoryHandle<F: TablePropertiesCollectorFactory> {
+    name: CString,
+    rep: F,
+}
+
+impl<F: TablePropertiesCollectorFactory> TablePropertiesCollectorFactoryHandle<F> {
+    fn new(name: &str, rep: F) -> TablePropertiesCollectorFactoryHandle<F> {
+        TablePropertiesCollectorFactoryHandle {
+            name: CString::new(name).unwrap(),
+            rep: rep,
+        }
+    }
+}
+
+extern "C" fn factory_name<F: TablePropertiesCollectorFactory>(handle: *mut c_void) -> *const c_char {
+    unsafe {
+        let handle = &mut *(handle as *mut TablePropertiesCollectorFactoryHandle<F>);
+        handle.name.as_ptr()
+    }
+}
+
+extern "C" fn factory_destruct<F: TablePropertiesCollectorFactory>(handle: *mut c_void) {
+    unsafe {
+        Box::from_raw(handle as *mut TablePropertiesCollectorFactoryHandle<F>);
+    }
+}
+
+pub extern "C" fn create_table_properties_collector<F: TablePropertiesCollectorFactory>(
+    handle: *mut c_void,
+    column_family_id: u32,
+    reason: DBTableFileCreationReason,
+) -> *mut DBTablePropertiesCollector {
+    unsafe {
+        let handle = &mut *(handle as *mut TablePropertiesCollectorFactoryHandle<F>);
+        let context = TablePropertiesCollectorFactoryContext {
+            column_family_id,
+            reason,
+        };
+        let collector = handle.rep.create_table_properties_collector(context);
+        let cname = handle.name.to_str().unwrap();
+        new_table_properties_collector(cname, collector)
+    }
+}
+
+pub unsafe fn new_table_properties_collector_factory<F: TablePropertiesCollectorFactory>(
+    cname: &str,
+    factory: F,
+) -> *mut DBTablePropertiesCollectorFactory {
+    let handle = TablePropertiesCollectorFactoryHandle::new(cname, factory);
+    crocksdb_ffi::crocksdb_table_properties_collector_factory_create(
+        Box::into_raw(Box::new(handle)) as *mut c_void,
+        factory_name::<F>,
+        factory_destruct::<F>,
+        create_table_properties_collector::<F>,
+    )
+}
+
+/// Registers `factory` on `options` so RocksDB creates a fresh collector for every
+/// table (SST) it builds, rather than sharing one collector across the whole DB.
+pub unsafe fn add_table_properties_collector_factory<F: TablePropertiesCollectorFactory>(
+    options: *mut DBOptions,
+    cname: &str,
+    factory: F,
+) {
+    let factory = new_table_properties_collector_factory(cname, factory);
+    crocksdb_ffi::crocksdb_options_add_table_properties_collector_factory(options, factory);
+}
+
+/// A built-in collector that uses the `block_add` sampling hook to estimate how
+/// compressible the data in a table is. RocksDB samples roughly 1-in-N data blocks
+/// and, for each sampled block, compresses it once with a fast codec (e.g. LZ4,
+/// Snappy) and once with a higher-ratio codec (e.g. Zstd, Zlib), handing this
+/// collector the raw byte count and both compressed byte counts. This collector
+/// sums those counts and skips blocks handed to it with both compressed counts
+/// zero (blocks that were not sampled); the sampling itself is driven by RocksDB.
+#[derive(Default)]
+pub struct CompressibilitySamplingCollector {
+    raw_bytes: u64,
+    compressed_bytes_fast: u64,
+    compressed_bytes_slow: u64,
+}
+
+impl CompressibilitySamplingCollector {
+    pub fn new() -> CompressibilitySamplingCollector {
+        CompressibilitySamplingCollector::default()
+    }
+}
+
+impl TablePropertiesCollector for CompressibilitySamplingCollector {
+    fn add(&mut self, _: &[u8], _: &[u8], _: DBEntryType, _: u64, _: u64) {}
+
+    fn block_add(
+        &mut self,
+        block_raw_bytes: u64,
+        block_compressed_bytes_fast: u64,
+        block_compressed_bytes_slow: u64,
+    ) {
+        // Blocks that were not sampled arrive with both compressed sizes zero;
+        // only sampled blocks are summed.
+        if block_compressed_bytes_fast == 0 && block_compressed_bytes_slow == 0 {
+            return;
+        }
+        self.raw_bytes += block_raw_bytes;
+        self.compressed_bytes_fast += block_compressed_bytes_fast;
+        self.compressed_bytes_slow += block_compressed_bytes_slow;
+    }
+
+    fn finish(&mut self) -> HashMap<Vec<u8>, Vec<u8>> {
+        let mut props = HashMap::new();
+        // No block was sampled for this table; leave the properties empty rather
+        // than reporting all-zero byte counts.
+        if self.raw_bytes == 0 {
+            return props;
+        }
+        // `rocksdb.*` is reserved for upstream's own `TablePropertiesNames`; use
+        // a distinct prefix for properties that aren't literally upstream ones.
+        props.insert(
+            b"rust-rocksdb.sample.raw.bytes".to_vec(),
+            self.raw_bytes.to_string().into_bytes(),
+        );
+        props.insert(
+            b"rust-rocksdb.sample.compressed.bytes.fast".to_vec(),
+            self.compressed_bytes_fast.to_string().into_bytes(),
+        );
+        props.insert(
+            b"rust-rocksdb.sample.compressed.bytes.slow".to_vec(),
+            self.compressed_bytes_slow.to_string().into_bytes(),
+        );
+        props
+    }
+}
+
+/// A built-in collector that tallies how many `Put`, `Delete`, `SingleDelete`
+/// and `Merge` entries a table holds; other `DBEntryType`s are not counted. Useful for compaction-policy heuristics (e.g. triggering
+/// `need_compact` when delete or merge-operand density is high) and for
+/// diagnosing tombstone buildup without writing a custom collector every time.
+#[derive(Default)]
+pub struct EntryTypeCountCollector {
+    puts: u64,
+    deletes: u64,
+    single_deletes: u64,
+    merges: u64,
+}
+
+impl EntryTypeCountCollector {
+    pub fn new() -> EntryTypeCountCollector {
+        EntryTypeCountCollector::default()
+    }
+}
+
+impl TablePropertiesCollector for EntryTypeCountCollector {
+    fn add(&mut self, _: &[u8], _: &[u8], entry_type: DBEntryType, _: u64, _: u64) {
+        match entry_type {
+            DBEntryType::Put => self.puts += 1,
+            DBEntryType::Delete => self.deletes += 1,
+            DBEntryType::SingleDelete => self.single_deletes += 1,
+            DBEntryType::Merge => self.merges += 1,
+            _ => {}
+        }
+    }
+
+    fn finish(&mut self) -> HashMap<Vec<u8>, Vec<u8>> {
+        let mut props = HashMap::new();
+        // `rocksdb.*` is reserved for upstream's own `TablePropertiesNames`, and
+        // upstream's `InternalKeyPropertiesCollector` runs on every table and
+        // already writes `rocksdb.merge.operands` into this same map (asserting
+        // the key isn't already present) — so even our merge count needs its
+        // own prefix rather than literally mirroring that name.
+        props.insert(
+            b"rust-rocksdb.entry.put".to_vec(),
+            self.puts.to_string().into_bytes(),
+        );
+        props.insert(
+            b"rust-rocksdb.entry.delete".to_vec(),
+            self.deletes.to_string().into_bytes(),
+        );
+        props.insert(
+            b"rust-rocksdb.entry.single_delete".to_vec(),
+            self.single_deletes.to_string().into_bytes(),
+        );
+        props.insert(
+            b"rust-rocksdb.entry.merge".to_vec(),
+            self.merges.to_string().into_bytes(),
+        );
+        props
+    }
+}
+
+/// Mirrors upstream's `DbStressTablePropertiesCollector`: `add` and `finish` do
+/// nothing, but `need_compact` returns `true` with probability 1-in-`N` under a
+/// seeded RNG. Exercises compaction paths deterministically, which is useful for
+/// fuzzing/stress harnesses built on this crate.
+pub struct RandomMarkCompactionCollector {
+    one_in_n: u32,
+    rng: StdRng,
+}
+
+impl RandomMarkCompactionCollector {
+    /// `one_in_n` must be greater than zero; `need_compact` is called from an
+    /// `extern "C"` callback, so panicking on an empty range there would unwind
+    /// across the FFI boundary and abort the process instead of failing cleanly.
+    pub fn new(one_in_n: u32, seed: u64) -> RandomMarkCompactionCollector {
+        assert!(one_in_n > 0, "one_in_n must be greater than zero");
+        RandomMarkCompactionCollector {
+            one_in_n,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl TablePropertiesCollector for RandomMarkCompactionCollector {
+    fn add(&mut self, _: &[u8], _: &[u8], _: DBEntryType, _: u64, _: u64) {}
+
+    fn finish(&mut self) -> HashMap<Vec<u8>, Vec<u8>> {
+        HashMap::new()
+    }
+
+    fn need_compact(&self) -> bool {
+        self.rng.clone().gen_range(0..self.one_in_n) == 0
+    }
+}
+
+/// Factory for [`RandomMarkCompactionCollector`]. Draws a fresh seed for every
+/// table it is asked to create a collector for, so each SST makes an independent
+/// random decision instead of the whole DB sharing one outcome.
+pub struct RandomMarkCompactionCollectorFactory {
+    one_in_n: u32,
+    seed_rng: Mutex<StdRng>,
+}
+
+impl RandomMarkCompactionCollectorFactory {
+    /// `one_in_n` must be greater than zero; see
+    /// `RandomMarkCompactionCollector::new` for why.
+    pub fn new(one_in_n: u32, seed: u64) -> RandomMarkCompactionCollectorFactory {
+        assert!(one_in_n > 0, "one_in_n must be greater than zero");
+        RandomMarkCompactionCollectorFactory {
+            one_in_n,
+            seed_rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+}
+
+impl TablePropertiesCollectorFactory for RandomMarkCompactionCollectorFactory {
+    fn create_table_properties_collector(
+        &self,
+        _context: TablePropertiesCollectorFactoryContext,
+    ) -> Box<dyn TablePropertiesCollector> {
+        let seed = self.seed_rng.lock().unwrap().gen();
+        Box::new(RandomMarkCompactionCollector::new(self.one_in_n, seed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compressibility_sampling_collector_empty_when_no_block_sampled() {
+        let mut collector = CompressibilitySamplingCollector::new();
+        assert!(collector.finish().is_empty());
+    }
+
+    #[test]
+    fn entry_type_count_collector_tallies_each_entry_type() {
+        let mut collector = EntryTypeCountCollector::new();
+        collector.add(b"k1", b"v1", DBEntryType::Put, 1, 0);
+        collector.add(b"k2", b"v2", DBEntryType::Delete, 2, 0);
+        collector.add(b"k3", b"v3", DBEntryType::SingleDelete, 3, 0);
+        collector.add(b"k4", b"v4", DBEntryType::Merge, 4, 0);
+
+        let props = collector.finish();
+        assert_eq!(props[&b"rust-rocksdb.entry.put".to_vec()], b"1");
+        assert_eq!(props[&b"rust-rocksdb.entry.delete".to_vec()], b"1");
+        assert_eq!(props[&b"rust-rocksdb.entry.single_delete".to_vec()], b"1");
+        assert_eq!(props[&b"rust-rocksdb.entry.merge".to_vec()], b"1");
+    }
+
+    #[test]
+    fn random_mark_compaction_factory_gives_each_collector_an_independent_seed() {
+        let factory = RandomMarkCompactionCollectorFactory::new(2, 7);
+        let context = || TablePropertiesCollectorFactoryContext {
+            column_family_id: 0,
+            reason: DBTableFileCreationReason::Flush,
+        };
+        let decisions: Vec<bool> = (0..64)
+            .map(|_| {
+                factory
+                    .create_table_properties_collector(context())
+                    .need_compact()
+            })
+            .collect();
+
+        // If every collector shared one seed (or one RNG draw), all 64 decisions
+        // would be identical; seeing both outcomes proves each call got its own.
+        assert!(decisions.iter().any(|d| *d));
+        assert!(decisions.iter().any(|d| !*d));
+    }
+}